@@ -1,8 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Args;
 
+pub mod changes;
+pub mod metadata;
+pub mod path;
 pub mod plumb;
+pub mod store;
 
 #[derive(Args, Clone, Debug)]
 pub struct Options {
@@ -10,6 +14,19 @@ pub struct Options {
     pub repo_path: PathBuf,
     #[clap(long)]
     pub disable_logging: bool,
+    /// Files at least this many bytes are memory-mapped instead of read
+    /// into a buffer.
+    #[clap(long, default_value_t = 1024 * 1024)]
+    pub mmap_threshold: u64,
+    /// Never memory-map files, even above `mmap_threshold`. Needed on
+    /// network filesystems `dj` fails to detect as such.
+    #[clap(long)]
+    pub disable_mmap: bool,
+    /// Passphrase to derive the object-encryption key from. When set,
+    /// objects written to the store are encrypted at rest and transparently
+    /// decrypted on read; when unset, objects are stored as plaintext.
+    #[clap(long)]
+    pub encryption_passphrase: Option<String>,
 }
 
 pub struct StorePath {
@@ -20,3 +37,42 @@ impl ToString for StorePath {
         self.hash.to_string()
     }
 }
+
+/// A checked-out repository: its store/metadata directory, the working
+/// directory its tracked files live under, and the branch currently checked
+/// out.
+pub struct Repository {
+    path: PathBuf,
+    work_dir: PathBuf,
+    branch: String,
+}
+
+impl Repository {
+    pub fn new(path: PathBuf, work_dir: PathBuf, branch: String) -> Repository {
+        Repository {
+            path,
+            work_dir,
+            branch,
+        }
+    }
+
+    /// The repository's store/metadata directory (e.g. `.tn`).
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The directory tracked files are checked out into.
+    pub fn work_dir(&self) -> &PathBuf {
+        &self.work_dir
+    }
+
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    /// `path` relative to [`work_dir`](Self::work_dir), or `None` if `path`
+    /// isn't inside it.
+    pub fn relative_path(&self, path: &PathBuf) -> Option<PathBuf> {
+        path.strip_prefix(&self.work_dir).ok().map(PathBuf::from)
+    }
+}
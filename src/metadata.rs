@@ -1,8 +1,9 @@
 use std::{collections::HashMap, path::PathBuf, str::FromStr};
 
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use thiserror::Error;
 
-use crate::Repository;
+use crate::{store::signing, Repository};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -12,6 +13,8 @@ pub enum Error {
     FailedToReadFile(std::io::Error, PathBuf),
     #[error("Failed to write to file '{1}'")]
     FailedToWriteFile(std::io::Error, PathBuf),
+    #[error("Failed to verify signature for '{1}'")]
+    VerificationFailed(#[source] signing::Error, PathBuf),
 }
 pub struct Metadata<'a> {
     repo: &'a Repository,
@@ -69,4 +72,35 @@ impl<'a> Metadata<'a> {
 
         Ok(())
     }
+
+    fn generations_sig_path(&self) -> PathBuf {
+        self.metadata_path().join("generations.sig")
+    }
+
+    /// Sign the current generations file with `signing_key`, writing the
+    /// detached signature to `metadata/generations.sig`.
+    pub fn sign_generations(&self, signing_key: &SigningKey) -> Result<(), Error> {
+        let generation_file_path = self.metadata_path().join("generations");
+        let bytes = std::fs::read(&generation_file_path)
+            .map_err(|e| Error::FailedToReadFile(e, generation_file_path))?;
+
+        let signature = signing::sign(signing_key, &bytes);
+        std::fs::write(self.generations_sig_path(), signature)
+            .map_err(|e| Error::FailedToWriteFile(e, self.generations_sig_path()))?;
+
+        Ok(())
+    }
+
+    /// Verify the generations file against its stored signature and
+    /// `verifying_key`.
+    pub fn verify_generations(&self, verifying_key: &VerifyingKey) -> Result<(), Error> {
+        let generation_file_path = self.metadata_path().join("generations");
+        let bytes = std::fs::read(&generation_file_path)
+            .map_err(|e| Error::FailedToReadFile(e, generation_file_path.clone()))?;
+        let signature = std::fs::read(self.generations_sig_path())
+            .map_err(|e| Error::FailedToReadFile(e, self.generations_sig_path()))?;
+
+        signing::verify(verifying_key, &bytes, &signature)
+            .map_err(|e| Error::VerificationFailed(e, generation_file_path))
+    }
 }
@@ -3,14 +3,25 @@ use std::path::PathBuf;
 use thiserror::Error;
 use tracing::{debug, error, trace};
 
-use crate::{Options, StorePath};
+use crate::{
+    store::{
+        backend::{filesystem, FilesystemBackend, ObjectBackend},
+        encryption::{self, EncryptingBackend},
+        mmap_io,
+    },
+    Options, StorePath,
+};
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Failed to read file '{}'", .1.display())]
     FailedToReadFile(#[source] std::io::Error, PathBuf),
-    #[error("Failed to write file '{}'", .1.display())]
-    FailedToWriteFile(#[source] std::io::Error, PathBuf),
+    #[error("Failed to write to store")]
+    FailedToWriteToStore(#[source] filesystem::Error),
+    #[error("Failed to write encrypted object to store")]
+    FailedToEncryptAndWriteToStore(#[source] encryption::Error<filesystem::Error>),
+    #[error("Failed to set up object encryption")]
+    Keyfile(#[source] encryption::KeyfileError),
     #[error("Store directory doesn't exist")]
     StoreDirDoesntExist,
 }
@@ -20,12 +31,13 @@ type Result<T> = std::result::Result<T, Error>;
 pub fn write_file_to_store(file: PathBuf, options: &Options) -> Result<StorePath> {
     debug!(file = %file.display(), "Writing file to store");
 
-    let bytes = std::fs::read(&file).map_err(|e| Error::FailedToReadFile(e, file.clone()))?;
+    let bytes = mmap_io::read(&file, &file, options.mmap_threshold, options.disable_mmap)
+        .map_err(|e| Error::FailedToReadFile(e, file.clone()))?;
     if !options.disable_logging {
         trace!("Read {} bytes from '{}'", bytes.len(), file.display());
     }
 
-    write_bytes_to_store(bytes, options)
+    write_bytes_to_store(bytes.to_vec(), options)
 }
 
 pub fn write_bytes_to_store(bytes: Vec<u8>, options: &Options) -> Result<StorePath> {
@@ -55,9 +67,27 @@ pub fn write_bytes_to_store_path(
         error!("Store directory doesn't exist!");
         return Err(Error::StoreDirDoesntExist);
     }
-    let path = options.repo_path.join("store").join(store_path.to_string());
 
-    std::fs::write(&path, &bytes).map_err(|e| Error::FailedToWriteFile(e, path.clone()))?;
+    let fs_backend = FilesystemBackend::new(store_dir.join("objects"));
+
+    match &options.encryption_passphrase {
+        Some(passphrase) => {
+            let keyfile_path = options.repo_path.join("metadata").join("keyfile");
+            let keyfile =
+                encryption::load_or_create_keyfile(&keyfile_path).map_err(Error::Keyfile)?;
+            let key = keyfile.derive_key(passphrase).map_err(Error::Keyfile)?;
+
+            EncryptingBackend::new(fs_backend, key)
+                .put(&store_path.hash, &bytes)
+                .map_err(Error::FailedToEncryptAndWriteToStore)?;
+        }
+        None => {
+            fs_backend
+                .put(&store_path.hash, &bytes)
+                .map_err(Error::FailedToWriteToStore)?;
+        }
+    }
+
     trace!(
         "Wrote '{}' bytes to store path '{}'",
         bytes.len(),
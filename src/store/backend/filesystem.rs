@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use blake3::Hash;
+use thiserror::Error;
+
+use crate::store::mmap_io::{self, DEFAULT_MMAP_THRESHOLD};
+
+use super::ObjectBackend;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to create object directory '{1}'")]
+    FailedToCreateDir(#[source] std::io::Error, PathBuf),
+    #[error("Failed to read object '{1}'")]
+    FailedToReadObject(#[source] std::io::Error, PathBuf),
+    #[error("Failed to write object '{1}'")]
+    FailedToWriteObject(#[source] std::io::Error, PathBuf),
+    #[error("Failed to read object directory '{1}'")]
+    FailedToReadDir(#[source] std::io::Error, PathBuf),
+    #[error("'{0}' is not a valid blake3 hash")]
+    InvalidObjectFileName(String),
+}
+
+/// An [`ObjectBackend`] that stores objects as individual files on the local
+/// filesystem, named after their hash, such as `store/objects`.
+pub struct FilesystemBackend {
+    root: PathBuf,
+    mmap_threshold: u64,
+    disable_mmap: bool,
+}
+
+impl FilesystemBackend {
+    pub fn new(root: PathBuf) -> FilesystemBackend {
+        FilesystemBackend {
+            root,
+            mmap_threshold: DEFAULT_MMAP_THRESHOLD,
+            disable_mmap: false,
+        }
+    }
+
+    /// A backend using `mmap_threshold`/`disable_mmap` for object reads
+    /// instead of the defaults, typically `Options::mmap_threshold`/
+    /// `Options::disable_mmap`.
+    pub fn with_mmap_options(
+        root: PathBuf,
+        mmap_threshold: u64,
+        disable_mmap: bool,
+    ) -> FilesystemBackend {
+        FilesystemBackend {
+            root,
+            mmap_threshold,
+            disable_mmap,
+        }
+    }
+
+    fn object_path(&self, hash: &Hash) -> PathBuf {
+        self.root.join(hash.to_string())
+    }
+}
+
+impl ObjectBackend for FilesystemBackend {
+    type Error = Error;
+
+    fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error> {
+        let path = self.object_path(hash);
+        // `self.root` (not `path`) is checked for network-filesystem-ness:
+        // it's stable for the lifetime of the backend, where individual
+        // object paths come and go as objects are written and vacuumed.
+        mmap_io::read(&path, &self.root, self.mmap_threshold, self.disable_mmap)
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| Error::FailedToReadObject(e, path))
+    }
+
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error> {
+        if !self.root.exists() {
+            std::fs::create_dir_all(&self.root)
+                .map_err(|e| Error::FailedToCreateDir(e, self.root.clone()))?;
+        }
+
+        let path = self.object_path(hash);
+        if path.exists() {
+            return Ok(());
+        }
+
+        std::fs::write(&path, bytes).map_err(|e| Error::FailedToWriteObject(e, path))
+    }
+
+    fn exists(&self, hash: &Hash) -> bool {
+        self.object_path(hash).exists()
+    }
+
+    fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error> {
+        if !self.root.exists() {
+            return Ok(Box::new(std::iter::empty()));
+        }
+
+        let hashes = self
+            .root
+            .read_dir()
+            .map_err(|e| Error::FailedToReadDir(e, self.root.clone()))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_str()?.to_string();
+                Hash::from_hex(&file_name).ok()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(hashes.into_iter()))
+    }
+}
@@ -0,0 +1,73 @@
+//! An [`ObjectBackend`] that is transparently plaintext or encrypted,
+//! depending on whether a key is supplied.
+//!
+//! [`FileStore`](super::super::file_store::FileStore) picks between a plain
+//! and an encrypting backend the same way internally; this is the
+//! re-usable version for code outside `FileStore` (currently [`gc`](super::super::gc)
+//! and [`migrate`](super::super::migrate)) that needs to read objects a
+//! store may have encrypted at rest.
+
+use std::path::PathBuf;
+
+use blake3::Hash;
+
+use crate::store::encryption::{self, EncryptingBackend};
+
+use super::{FilesystemBackend, ObjectBackend};
+
+pub enum MaybeEncryptedBackend {
+    Plain(FilesystemBackend),
+    Encrypted(EncryptingBackend<FilesystemBackend>),
+}
+
+impl MaybeEncryptedBackend {
+    /// A filesystem backend rooted at `objects_dir`, encrypting with `key`
+    /// if one is given.
+    pub fn new(objects_dir: PathBuf, key: Option<[u8; encryption::KEY_LEN]>) -> MaybeEncryptedBackend {
+        let fs_backend = FilesystemBackend::new(objects_dir);
+        match key {
+            Some(key) => MaybeEncryptedBackend::Encrypted(EncryptingBackend::new(fs_backend, key)),
+            None => MaybeEncryptedBackend::Plain(fs_backend),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Plain(super::filesystem::Error),
+    #[error(transparent)]
+    Encrypted(encryption::Error<super::filesystem::Error>),
+}
+
+impl ObjectBackend for MaybeEncryptedBackend {
+    type Error = Error;
+
+    fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error> {
+        match self {
+            MaybeEncryptedBackend::Plain(b) => b.get(hash).map_err(Error::Plain),
+            MaybeEncryptedBackend::Encrypted(b) => b.get(hash).map_err(Error::Encrypted),
+        }
+    }
+
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error> {
+        match self {
+            MaybeEncryptedBackend::Plain(b) => b.put(hash, bytes).map_err(Error::Plain),
+            MaybeEncryptedBackend::Encrypted(b) => b.put(hash, bytes).map_err(Error::Encrypted),
+        }
+    }
+
+    fn exists(&self, hash: &Hash) -> bool {
+        match self {
+            MaybeEncryptedBackend::Plain(b) => b.exists(hash),
+            MaybeEncryptedBackend::Encrypted(b) => b.exists(hash),
+        }
+    }
+
+    fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error> {
+        match self {
+            MaybeEncryptedBackend::Plain(b) => b.list().map_err(Error::Plain),
+            MaybeEncryptedBackend::Encrypted(b) => b.list().map_err(Error::Encrypted),
+        }
+    }
+}
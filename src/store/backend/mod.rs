@@ -0,0 +1,37 @@
+//! Storage backends for content-addressed objects.
+//!
+//! [`ObjectBackend`] is the lowest-level abstraction `dj` uses to get bytes
+//! in and out of a store: everything above it (chunk manifests, [`FileMeta`](super::FileMeta)
+//! steps) only ever deals in hashes, so the bytes for those hashes can live
+//! on the local filesystem, on a remote object store, or behind any other
+//! driver that implements this trait.
+
+use blake3::Hash;
+
+pub mod filesystem;
+pub mod maybe_encrypted;
+pub mod s3;
+
+pub use filesystem::FilesystemBackend;
+pub use maybe_encrypted::MaybeEncryptedBackend;
+pub use s3::S3Backend;
+
+/// A place objects can be fetched from and stored to, keyed by their blake3
+/// hash.
+pub trait ObjectBackend {
+    type Error: std::error::Error;
+
+    /// Fetch the bytes stored under `hash`.
+    fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error>;
+
+    /// Store `bytes` under `hash`. Implementations should skip the write if
+    /// `hash` is already present, since the same hash always means the same
+    /// bytes.
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Whether an object for `hash` is already present.
+    fn exists(&self, hash: &Hash) -> bool;
+
+    /// List the hashes of every object currently stored.
+    fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error>;
+}
@@ -0,0 +1,92 @@
+use blake3::Hash;
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+use thiserror::Error;
+
+use super::ObjectBackend;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to reach S3-compatible backend")]
+    Request(#[source] S3Error),
+    #[error("Object '{0}' not found")]
+    NotFound(Hash),
+    #[error("Remote key '{0}' is not a valid blake3 hash")]
+    InvalidObjectKey(String),
+}
+
+/// An [`ObjectBackend`] backed by an S3-compatible object store. Objects are
+/// keyed by their blake3 hash under `prefix/`, e.g. `prefix/<hash>`.
+pub struct S3Backend {
+    bucket: Bucket,
+    prefix: String,
+}
+
+impl S3Backend {
+    pub fn new(
+        bucket_name: &str,
+        region: Region,
+        credentials: Credentials,
+        prefix: String,
+    ) -> Result<S3Backend, Error> {
+        let bucket = Bucket::new(bucket_name, region, credentials).map_err(Error::Request)?;
+        Ok(S3Backend { bucket, prefix })
+    }
+
+    fn key(&self, hash: &Hash) -> String {
+        format!("{}/{}", self.prefix.trim_end_matches('/'), hash)
+    }
+}
+
+impl ObjectBackend for S3Backend {
+    type Error = Error;
+
+    fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error> {
+        let response = self
+            .bucket
+            .get_object_blocking(self.key(hash))
+            .map_err(Error::Request)?;
+
+        if response.status_code() == 404 {
+            return Err(Error::NotFound(*hash));
+        }
+
+        Ok(response.into_bytes())
+    }
+
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.exists(hash) {
+            return Ok(());
+        }
+
+        self.bucket
+            .put_object_blocking(self.key(hash), bytes)
+            .map_err(Error::Request)?;
+
+        Ok(())
+    }
+
+    fn exists(&self, hash: &Hash) -> bool {
+        self.bucket
+            .head_object_blocking(self.key(hash))
+            .is_ok_and(|(_, code)| code == 200)
+    }
+
+    fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error> {
+        let prefix = format!("{}/", self.prefix.trim_end_matches('/'));
+        let pages = self
+            .bucket
+            .list_blocking(prefix.clone(), None)
+            .map_err(Error::Request)?;
+
+        let hashes = pages
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .filter_map(|object| {
+                let key = object.key.strip_prefix(&prefix)?.to_string();
+                Hash::from_hex(&key).ok()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Box::new(hashes.into_iter()))
+    }
+}
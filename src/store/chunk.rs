@@ -0,0 +1,245 @@
+//! Content-defined chunking (FastCDC-style) and the manifest format that
+//! references the resulting chunks.
+//!
+//! Rather than storing a whole file as a single object, a file's bytes are
+//! split into content-defined chunks along a rolling "Gear" hash. Because
+//! boundaries are chosen from the content itself rather than fixed offsets,
+//! small edits to a large file only change the chunks around the edit, so
+//! unaffected chunks are shared with every other step and file that already
+//! wrote them.
+
+use blake3::Hash;
+
+/// Smallest chunk FastCDC will ever cut, besides EOF.
+pub const MIN_CHUNK_SIZE: usize = 2 * 1024;
+/// Size at which the cut mask relaxes from `MASK_S` to `MASK_L`.
+pub const NORMAL_CHUNK_SIZE: usize = 8 * 1024;
+/// Largest chunk FastCDC will ever cut; a boundary is forced here.
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+// Stricter mask (more 1-bits) used below `NORMAL_CHUNK_SIZE`, so a boundary is
+// less likely, pushing chunks to grow. The looser mask above it makes a
+// boundary more likely, so chunks settle around `NORMAL_CHUNK_SIZE` on average.
+const MASK_S: u64 = 0x0003_5907_0353_0000;
+const MASK_L: u64 = 0x0000_d900_0353_0000;
+
+/// A single content-defined chunk produced by [`chunks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef {
+    pub hash: Hash,
+    pub len: u64,
+}
+
+/// Split `bytes` into content-defined chunks using a FastCDC-style rolling
+/// Gear hash, hashing each chunk with blake3 as it is cut.
+pub fn chunks(bytes: &[u8]) -> Vec<ChunkRef> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < bytes.len() {
+        let end = cut_point(&bytes[start..]);
+        let chunk = &bytes[start..start + end];
+        chunks.push(ChunkRef {
+            hash: blake3::hash(chunk),
+            len: chunk.len() as u64,
+        });
+        start += end;
+    }
+
+    chunks
+}
+
+/// Find the offset of the next chunk boundary in `bytes`, relative to the
+/// start of `bytes`. Always returns at least `MIN_CHUNK_SIZE` (unless `bytes`
+/// is shorter than that, in which case it returns `bytes.len()`) and never
+/// more than `MAX_CHUNK_SIZE`.
+fn cut_point(bytes: &[u8]) -> usize {
+    if bytes.len() <= MIN_CHUNK_SIZE {
+        return bytes.len();
+    }
+
+    let max = bytes.len().min(MAX_CHUNK_SIZE);
+
+    let mut hash: u64 = 0;
+    for i in MIN_CHUNK_SIZE..max {
+        hash = (hash << 1).wrapping_add(GEAR[bytes[i] as usize]);
+
+        let mask = if i < NORMAL_CHUNK_SIZE { MASK_S } else { MASK_L };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+
+    max
+}
+
+/// Version tag for the manifest format below. Unversioned, untagged objects
+/// predate chunking: a step's object is the raw file bytes with no header,
+/// so `Manifest::from_bytes` falls back to treating the bytes as a single
+/// legacy object when the tag doesn't match.
+const MANIFEST_VERSION_V2: u8 = 2;
+
+/// Fixed size of one manifest entry: a blake3 hash followed by a big-endian
+/// chunk length.
+const ENTRY_SIZE: usize = blake3::OUT_LEN + 8;
+
+/// An ordered list of chunks that make up a file, stored as the object a
+/// step's `ObjectPath` points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl Manifest {
+    pub fn new(chunks: Vec<ChunkRef>) -> Manifest {
+        Manifest { chunks }
+    }
+
+    /// Serialize to the versioned v2 manifest layout:
+    /// `[version: u8] ([hash: 32 bytes][len: u64 be])*`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + self.chunks.len() * ENTRY_SIZE);
+        out.push(MANIFEST_VERSION_V2);
+        for chunk in &self.chunks {
+            out.extend_from_slice(chunk.hash.as_bytes());
+            out.extend_from_slice(&chunk.len.to_be_bytes());
+        }
+        out
+    }
+
+    /// Parse a v2 manifest, returning `None` if `bytes` isn't tagged as one
+    /// (i.e. it's a pre-chunking, v1 raw object).
+    pub fn from_bytes(bytes: &[u8]) -> Option<Manifest> {
+        let (&version, rest) = bytes.split_first()?;
+        if version != MANIFEST_VERSION_V2 || rest.len() % ENTRY_SIZE != 0 {
+            return None;
+        }
+
+        let chunks = rest
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                let hash = Hash::from_bytes(entry[..blake3::OUT_LEN].try_into().unwrap());
+                let len = u64::from_be_bytes(entry[blake3::OUT_LEN..].try_into().unwrap());
+                ChunkRef { hash, len }
+            })
+            .collect();
+
+        Some(Manifest { chunks })
+    }
+}
+
+// 256 fixed pseudo-random u64s used by the Gear rolling hash. Values don't
+// need any particular structure, just a wide, fixed spread of bits.
+#[rustfmt::skip]
+const GEAR: [u64; 256] = [
+    0x2e3149f6a8baf845, 0xe247040eeef9806d, 0xace7c49189d87ef4, 0x1c8fb549c48de9ec,
+    0x767832b10b4af9c6, 0xff4a16e1fc4b29c9, 0x74f072749dcc772c, 0x0d31359f8969f62c,
+    0x4a6223ef45c34d2b, 0x1f7175a0fab79026, 0x5c9f2b42a13eb7ca, 0x28f0f446f7a29625,
+    0xb29c965ae6f34f84, 0x08fe8b05f7b33f73, 0xfe5a19dffb85905e, 0xe269fc67dcfd23e2,
+    0x7d40c9eb968b8b97, 0x58e44aadac443372, 0x0bea98ee32622be1, 0x9a24784d1a339aa9,
+    0x71b21baec8398c22, 0xee720f1698883430, 0x32422b0d95c73c7e, 0x6e723f6d1acf705f,
+    0xace13c7998d150b8, 0xa0da40555447b79d, 0x6006e205944771d1, 0x1a852e1465ca681d,
+    0xfab55e48e3b8a396, 0xcdc693ffdbbb55b5, 0x768d64f6aad41ca3, 0xcc459f4760e7db1c,
+    0xf023aafff0a02501, 0xbed51340c194e7b7, 0xf3732e6635431ec9, 0x29e405d65714b99d,
+    0xe6dcd47d181a77b0, 0xa92b5e246c5024a1, 0x55985e3b1984d3c1, 0x3647cb67def12aa2,
+    0xc012c749fbc47fbf, 0x3691ae3e560d909f, 0x95064527a8fc2665, 0x46b7343b7234171c,
+    0x5ae112cd3dea4e90, 0x19d06bb4b110f475, 0x9ce765f23b6dff7d, 0x28e17b78248e3360,
+    0x2f3a9d2d11a950c6, 0x68edb06520dc3865, 0x57d84e3a6ea4b52d, 0x9846c03646d6a308,
+    0x3123d64475a6a05c, 0x15b78994649162f1, 0x88e55283db3189cc, 0x7b39c5b6533f04ab,
+    0xde9f234f56e0e36f, 0x6780da48aa3607b1, 0xa72a4dfa87304270, 0x235727fed0850bc8,
+    0xf872bac1f9b55b76, 0x08baad4ce0b86ca2, 0xa00612f2c6488e5b, 0x55fd90298528deae,
+    0xe4366150337ae0d7, 0xa42b355e78cb8549, 0x7a192a26e574727d, 0xcb0cf326a349824b,
+    0xbf51043b3087c9b0, 0xa66c5796317eeadc, 0xfd4a089e2383a6ec, 0x12a3fd2f3b30946d,
+    0x4159c2a388321c02, 0x23680946b63cd903, 0x66a230cccbc58a33, 0xc0e63cd59456dc51,
+    0x4395e3c114a79de6, 0x7bcb70ebe85bd56d, 0x2031e45d49b9ba6a, 0x32585aeb6343a369,
+    0x6f710b77bdca0acd, 0x7b91511608063473, 0xabe474ec273a41d3, 0xd1e77b069264520e,
+    0xc42ac62bdb0f2128, 0x6fbfe025e721bd6f, 0xb5060665d033709e, 0x580f2e93a40ad6af,
+    0x8694381f287e2eb0, 0x8dfbf22166161857, 0x461443ceb86902c1, 0xf45477ba713aad97,
+    0x8334acc05f014030, 0x16653ff7e4cc22bc, 0x59214fb42bcbf1f1, 0x11c59dcdd2f57d2e,
+    0x6dc1d0e29c41a8bf, 0x2ee9de7570595e8f, 0x14496a7f6524bf9b, 0xa3684b503d3131dc,
+    0x8e83c385a86ace06, 0x27abd45eb4f8a33e, 0x36098e9321044345, 0xf870db451e139d90,
+    0xe94635b1a609dd7a, 0x8d624589909dc98c, 0xbc46e2600bcc5ed2, 0xf9cfb0a8b0912d34,
+    0x0dd2b826ea01a1f0, 0x2da7593975ebe310, 0x5c281921c34f6a02, 0x3cd30898893762b3,
+    0x0ede148d32f6139c, 0x2bac3dbfa362e48c, 0x116230836a8a4157, 0x819d0885042b7234,
+    0x7e23693f21ad9f7e, 0x3439702061995977, 0xd11c924eb48f7cc9, 0xcff05ff5d9429c3c,
+    0x9953bc5f6066becf, 0x8f74b4ea9190e641, 0x572b8c2e0e36333c, 0xedf90a794856448f,
+    0xb147d4149047d1a6, 0x675beb1f83f30b15, 0x56a336fb6d93f6dc, 0x2dca178e2ffb4b35,
+    0x105023a14813ee7e, 0xe4ef88ddcfdee920, 0x751a15e8027d572a, 0x832bccc152b51195,
+    0x82aa5d9d3cc710bc, 0xa088fbecf08e06dd, 0x49bc8bd942258926, 0x0c1f1af3f1c52d0e,
+    0x37e0c9a3a6c8d0f5, 0x2d8dbdd83cb2fa68, 0x87825394ab3ed250, 0x895abce2c4d9594f,
+    0xa744905804c49f42, 0x5de33a795a490ef6, 0xfad528ac288e4ac4, 0x03845cf5af1056f4,
+    0xb7161c99d2c2b8b9, 0x9e8064b1ff43a136, 0x8c957475c1b0b70a, 0xeb2782ca78aefcab,
+    0x366ad7dfc8deb95e, 0x88f1f897f0812f84, 0x44ce71e0beae99c9, 0x62bb2c83a6c09614,
+    0xd3ed0b0789cebdb2, 0xed97c367e20bcf84, 0xbe9e57697ad4a708, 0xf4183ab61d470ddd,
+    0xd84cc1ad7a8c274d, 0xf8f3adff4cf4701b, 0x8874ca73de54a0de, 0x91399b566f15dad8,
+    0xae46d626f9c52be5, 0x229dcc7f5d7312fc, 0xd3e661290fbc0777, 0xaaa9dffe1f5fd3f4,
+    0x30dd458e44decb67, 0x14b334ae54966d03, 0xa0ad5fc860dcbc94, 0xd9f1d9b4c3a90cce,
+    0x62a470aebfcab8f1, 0xcc38b561a491ec33, 0x33a3284c9fb7d99b, 0xb2c23d654b091346,
+    0x121191e5b8db960c, 0x0401114996dc78ee, 0xcaf650dc9d70b82b, 0x060e409a784ae4d6,
+    0xdd3914e36b214aba, 0x673d7ed6c54bfb79, 0x84ac10275f682143, 0xceabf49956d70b82,
+    0x79224d826e4a6026, 0xdd2cf84fae6e3339, 0x2cf6ae32cb510f21, 0xa2f64c81147939ff,
+    0x786f0e000510881d, 0xf64805ac6d3a5d46, 0x8e8edba735542edc, 0x4acbcc5f6b4c70d8,
+    0xa23b3581f236bc02, 0xc764480c07c76358, 0x5a944b4a141773e4, 0x0279f9f5f2806ed7,
+    0x07c7d877dacfe053, 0x58e3b713229e29a3, 0x208b995eaad2953f, 0xc6aa88f6fb7ffc38,
+    0x47a64ff749caafb3, 0xb295ab11133362ec, 0xafdbe7bab429a842, 0xe283139e51f917bd,
+    0x7db90bc464d66e27, 0x9eb187f90018a726, 0x4a7c0d647c0f8d30, 0x0345e1ce36769c69,
+    0x5bbf6f306b429467, 0xcdddea277437170c, 0x9cf0620ef9b807d5, 0xce15db5c3c28047a,
+    0xa4d254700b398807, 0x5da95e2529e9861d, 0xa27bdc6dbc752bd1, 0x283f506a19bfd51c,
+    0x81df07ee966fedc3, 0x1d32b50bdb5ad504, 0x84f11961c65bf71d, 0x4585d6c2e41e531d,
+    0x420763658126c9bc, 0x2dc470e9b7fd6042, 0xe73091f4bcae968c, 0x426dc21d3e4b2a75,
+    0xfa68729391722131, 0xfcef92d862fe64d5, 0x2696a257f81a43c0, 0x565168d139ea58f0,
+    0x0ccaa8de9e45b16d, 0x438f7f3e5effa0c0, 0xda533bd564377c95, 0x5af760d5eb22678d,
+    0x769a6063617c2c3d, 0x33141250fae93df7, 0xb5b9ccfd2cee6c38, 0xdd205660d5742fc6,
+    0x00b2c3e92837e277, 0xc1526337f000e5f9, 0x6ec622c8c074e135, 0xd6c53ccc141e9ebb,
+    0x79a23d8d9380f9ad, 0x33385bb91d9ab410, 0x2768a81074d3e268, 0x9bc42a1b2b714f05,
+    0x2c61d8197171a2e5, 0x3303108ea366dde5, 0x23f8354f42247daa, 0x0a4f1df99b0f44e2,
+    0x5b915073be8bfb60, 0xdb5be4f5f537956d, 0xef0b7571ac6bd21b, 0x413f3a04fa57a1ea,
+    0x83e28487e021355e, 0x35b35c36f2ae56b1, 0xed52f42dbc605cae, 0x1182fdacd0399cd1,
+    0x075dbe2a938c8a35, 0xa0fd8cfc991373c6, 0xfa82b9bcabbd8f39, 0xb12f249631e32301,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_reassemble_to_original_bytes() {
+        let bytes = (0..200_000u32).flat_map(|i| i.to_le_bytes()).collect::<Vec<u8>>();
+
+        let chunks = chunks(&bytes);
+        assert!(chunks.len() > 1);
+
+        let mut reassembled = Vec::with_capacity(bytes.len());
+        let mut offset = 0;
+        for chunk in &chunks {
+            assert!(chunk.len as usize <= MAX_CHUNK_SIZE);
+
+            let slice = &bytes[offset..offset + chunk.len as usize];
+            assert_eq!(blake3::hash(slice), chunk.hash);
+            reassembled.extend_from_slice(slice);
+            offset += chunk.len as usize;
+        }
+
+        assert_eq!(reassembled, bytes);
+    }
+
+    #[test]
+    fn manifest_round_trips() {
+        let manifest = Manifest::new(vec![
+            ChunkRef { hash: blake3::hash(b"a"), len: 1 },
+            ChunkRef { hash: blake3::hash(b"bb"), len: 2 },
+        ]);
+
+        let bytes = manifest.to_bytes();
+        let parsed = Manifest::from_bytes(&bytes).unwrap();
+
+        assert_eq!(manifest, parsed);
+    }
+
+    #[test]
+    fn legacy_unversioned_bytes_arent_parsed_as_a_manifest() {
+        // A pre-chunking object is just raw file bytes, with no tag byte.
+        let legacy_object = b"plain file contents".to_vec();
+        assert_eq!(Manifest::from_bytes(&legacy_object), None);
+    }
+}
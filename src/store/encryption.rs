@@ -0,0 +1,298 @@
+//! Encryption at rest for stored objects.
+//!
+//! The store stays content-addressed over the *plaintext*: a step's hash is
+//! computed before encryption and never changes, so dedup still works and
+//! hashes are stable across a repo's lifetime. What changes is what's on
+//! disk at that hash: `nonce || ciphertext || tag` instead of the plaintext
+//! itself. [`EncryptingBackend`] wraps any other [`ObjectBackend`] to add
+//! this transparently.
+
+use std::path::{Path, PathBuf};
+
+use argon2::Argon2;
+use blake3::Hash;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng},
+    AeadCore, XChaCha20Poly1305, XNonce,
+};
+use thiserror::Error;
+
+use super::backend::ObjectBackend;
+
+pub const KEY_LEN: usize = 32;
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// Length of the Poly1305 authentication tag XChaCha20-Poly1305 appends to
+/// every ciphertext.
+const TAG_LEN: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum Error<BE> {
+    #[error(transparent)]
+    Backend(BE),
+    /// A wrong passphrase and a tampered object both fail AEAD
+    /// authentication identically, so they can't be told apart here.
+    #[error("Failed to decrypt object: wrong passphrase, or the object has been tampered with")]
+    DecryptionFailed,
+    #[error("Stored object is too short to contain a nonce and auth tag")]
+    Truncated,
+    #[error("Failed to encrypt object")]
+    Encryption,
+}
+
+#[derive(Debug, Error)]
+pub enum KeyfileError {
+    #[error("Failed to derive key from passphrase")]
+    KeyDerivation,
+    #[error("Failed to read keyfile '{1}'")]
+    FailedToReadKeyfile(#[source] std::io::Error, PathBuf),
+    #[error("Failed to write keyfile '{1}'")]
+    FailedToWriteKeyfile(#[source] std::io::Error, PathBuf),
+    #[error("Keyfile '{0}' is malformed")]
+    MalformedKeyfile(PathBuf),
+    #[error("Failed to create directory '{1}' for keyfile")]
+    FailedToCreateDir(#[source] std::io::Error, PathBuf),
+}
+
+/// The Argon2id salt and cost parameters used to derive a repo's object
+/// encryption key from a passphrase, stored at `metadata/keyfile`.
+pub struct Keyfile {
+    pub salt: [u8; SALT_LEN],
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Keyfile {
+    /// Generate a fresh salt with sane default Argon2id cost parameters.
+    pub fn generate() -> Keyfile {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Keyfile {
+            salt,
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Keyfile, KeyfileError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| KeyfileError::FailedToReadKeyfile(e, path.to_path_buf()))?;
+        Keyfile::from_bytes(&bytes).ok_or_else(|| KeyfileError::MalformedKeyfile(path.to_path_buf()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), KeyfileError> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| KeyfileError::FailedToWriteKeyfile(e, path.to_path_buf()))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SALT_LEN + 12);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.m_cost.to_be_bytes());
+        out.extend_from_slice(&self.t_cost.to_be_bytes());
+        out.extend_from_slice(&self.p_cost.to_be_bytes());
+        out
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Keyfile> {
+        if bytes.len() != SALT_LEN + 12 {
+            return None;
+        }
+
+        let mut salt = [0; SALT_LEN];
+        salt.copy_from_slice(&bytes[..SALT_LEN]);
+        let m_cost = u32::from_be_bytes(bytes[SALT_LEN..SALT_LEN + 4].try_into().unwrap());
+        let t_cost = u32::from_be_bytes(bytes[SALT_LEN + 4..SALT_LEN + 8].try_into().unwrap());
+        let p_cost = u32::from_be_bytes(bytes[SALT_LEN + 8..SALT_LEN + 12].try_into().unwrap());
+
+        Some(Keyfile {
+            salt,
+            m_cost,
+            t_cost,
+            p_cost,
+        })
+    }
+
+    /// Derive the object encryption key from `passphrase` using Argon2id.
+    pub fn derive_key(&self, passphrase: &str) -> Result<[u8; KEY_LEN], KeyfileError> {
+        let params = argon2::Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|_| KeyfileError::KeyDerivation)?;
+        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        let mut key = [0; KEY_LEN];
+        argon2
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+            .map_err(|_| KeyfileError::KeyDerivation)?;
+
+        Ok(key)
+    }
+}
+
+/// Load the keyfile at `path`, generating and persisting a fresh one on
+/// first use.
+pub fn load_or_create_keyfile(path: &Path) -> Result<Keyfile, KeyfileError> {
+    if path.exists() {
+        Keyfile::read(path)
+    } else {
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| KeyfileError::FailedToCreateDir(e, parent.to_path_buf()))?;
+            }
+        }
+
+        let keyfile = Keyfile::generate();
+        keyfile.write(path)?;
+        Ok(keyfile)
+    }
+}
+
+/// An [`ObjectBackend`] that transparently encrypts objects written to, and
+/// decrypts objects read from, an inner backend. Hashes passed to `put`/`get`
+/// are always over plaintext; only the bytes on the wire/disk are encrypted.
+pub struct EncryptingBackend<B: ObjectBackend> {
+    inner: B,
+    cipher: XChaCha20Poly1305,
+}
+
+impl<B: ObjectBackend> EncryptingBackend<B> {
+    pub fn new(inner: B, key: [u8; KEY_LEN]) -> EncryptingBackend<B> {
+        EncryptingBackend {
+            inner,
+            cipher: XChaCha20Poly1305::new((&key).into()),
+        }
+    }
+}
+
+impl<B: ObjectBackend> ObjectBackend for EncryptingBackend<B> {
+    type Error = Error<B::Error>;
+
+    fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error> {
+        let bytes = self.inner.get(hash).map_err(Error::Backend)?;
+        if bytes.len() < NONCE_LEN + TAG_LEN {
+            return Err(Error::Truncated);
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::DecryptionFailed)
+    }
+
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.inner.exists(hash) {
+            return Ok(());
+        }
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, bytes)
+            .map_err(|_| Error::Encryption)?;
+
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+
+        self.inner.put(hash, &out).map_err(Error::Backend)
+    }
+
+    fn exists(&self, hash: &Hash) -> bool {
+        self.inner.exists(hash)
+    }
+
+    fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error> {
+        self.inner.list().map_err(Error::Backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap};
+
+    use super::*;
+
+    /// An in-memory `ObjectBackend`, so these tests don't need to touch the
+    /// filesystem.
+    #[derive(Default)]
+    struct MemoryBackend(RefCell<HashMap<Hash, Vec<u8>>>);
+
+    #[derive(Debug, Error)]
+    #[error("object not found")]
+    struct MemoryBackendError;
+
+    impl ObjectBackend for MemoryBackend {
+        type Error = MemoryBackendError;
+
+        fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error> {
+            self.0.borrow().get(hash).cloned().ok_or(MemoryBackendError)
+        }
+
+        fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.0
+                .borrow_mut()
+                .entry(*hash)
+                .or_insert_with(|| bytes.to_vec());
+            Ok(())
+        }
+
+        fn exists(&self, hash: &Hash) -> bool {
+            self.0.borrow().contains_key(hash)
+        }
+
+        fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error> {
+            let hashes = self.0.borrow().keys().cloned().collect::<Vec<_>>();
+            Ok(Box::new(hashes.into_iter()))
+        }
+    }
+
+    fn key_from(passphrase: &str) -> [u8; KEY_LEN] {
+        Keyfile::generate().derive_key(passphrase).unwrap()
+    }
+
+    #[test]
+    fn round_trips_through_encryption() {
+        let backend = EncryptingBackend::new(MemoryBackend::default(), key_from("correct horse"));
+
+        let hash = blake3::hash(b"plaintext object");
+        backend.put(&hash, b"plaintext object").unwrap();
+
+        // The bytes on the inner backend are not the plaintext.
+        let stored = backend.inner.get(&hash).unwrap();
+        assert_ne!(stored, b"plaintext object");
+
+        assert_eq!(backend.get(&hash).unwrap(), b"plaintext object");
+    }
+
+    #[test]
+    fn wrong_key_fails_distinctly_from_truncation() {
+        let write_backend = EncryptingBackend::new(MemoryBackend::default(), key_from("right"));
+        let hash = blake3::hash(b"secret");
+        write_backend.put(&hash, b"secret").unwrap();
+        let ciphertext = write_backend.inner.get(&hash).unwrap();
+
+        let read_backend = EncryptingBackend::new(MemoryBackend::default(), key_from("wrong"));
+        read_backend.inner.put(&hash, &ciphertext).unwrap();
+
+        assert!(matches!(
+            read_backend.get(&hash).unwrap_err(),
+            Error::DecryptionFailed
+        ));
+    }
+
+    #[test]
+    fn truncated_object_is_reported_distinctly() {
+        let backend = EncryptingBackend::new(MemoryBackend::default(), key_from("right"));
+        let hash = blake3::hash(b"x");
+        // Shorter than a nonce + tag, but non-empty, so it must not be
+        // confused with a failed decryption.
+        backend
+            .inner
+            .put(&hash, &vec![0u8; NONCE_LEN + TAG_LEN - 1])
+            .unwrap();
+
+        assert!(matches!(backend.get(&hash).unwrap_err(), Error::Truncated));
+    }
+}
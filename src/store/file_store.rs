@@ -1,7 +1,8 @@
-use std::{fs::File, io::BufReader, path::PathBuf};
+use std::path::PathBuf;
 
 use base64::{engine::general_purpose::URL_SAFE, Engine};
 use blake3::Hash;
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use thiserror::Error;
 
 use crate::{
@@ -9,7 +10,13 @@ use crate::{
     Repository,
 };
 
-use super::{FileMeta, Store};
+use super::{
+    backend,
+    backend::{FilesystemBackend, ObjectBackend},
+    chunk::{self, Manifest},
+    encryption::{self, EncryptingBackend},
+    mmap_io, signing, FileMeta, Store,
+};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -20,40 +27,177 @@ pub enum Error {
     #[error("Path '{0}' does not exist")]
     PathDoesntExist(RepoPath),
     #[error("Failed to read object: '{1}'")]
-    FailedToReadObject(#[source] std::io::Error, ObjectPath),
+    FailedToReadObject(#[source] BackendError, ObjectPath),
     #[error("Failed to write to object: '{1}'")]
-    FailedToWriteToObject(#[source] std::io::Error, ObjectPath),
+    FailedToWriteToObject(#[source] BackendError, ObjectPath),
     #[error("Failed to create store directory")]
     FailedToCreateStoreDir(#[source] std::io::Error),
     #[error("Failed to read file: '{1}'")]
     FailedToReadFile(#[source] std::io::Error, PathBuf),
     #[error("Failed to read a store directory: '{1}'")]
     FailedToReadStoreDir(#[source] std::io::Error, PathBuf),
-    #[error("Failed to copy file '{1}' to '{2}'")]
-    FailedToCopyFile(#[source] std::io::Error, PathBuf, PathBuf),
     #[error("Path isn't in repository: '{0}'")]
     PathIsntInRepository(PathBuf),
+    #[error("Failed to verify signature for metadata file: '{1}'")]
+    VerificationFailed(#[source] signing::Error, PathBuf),
+    #[error("Failed to set up object encryption")]
+    Keyfile(#[source] encryption::KeyfileError),
+}
+
+/// Either a plaintext or an encrypted object backend, chosen at `FileStore`
+/// construction time depending on whether it was given an encryption key.
+enum Backend {
+    Plain(FilesystemBackend),
+    Encrypted(EncryptingBackend<FilesystemBackend>),
+}
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error(transparent)]
+    Plain(backend::filesystem::Error),
+    #[error(transparent)]
+    Encrypted(encryption::Error<backend::filesystem::Error>),
+}
+
+impl ObjectBackend for Backend {
+    type Error = BackendError;
+
+    fn get(&self, hash: &Hash) -> Result<Vec<u8>, Self::Error> {
+        match self {
+            Backend::Plain(b) => b.get(hash).map_err(BackendError::Plain),
+            Backend::Encrypted(b) => b.get(hash).map_err(BackendError::Encrypted),
+        }
+    }
+
+    fn put(&self, hash: &Hash, bytes: &[u8]) -> Result<(), Self::Error> {
+        match self {
+            Backend::Plain(b) => b.put(hash, bytes).map_err(BackendError::Plain),
+            Backend::Encrypted(b) => b.put(hash, bytes).map_err(BackendError::Encrypted),
+        }
+    }
+
+    fn exists(&self, hash: &Hash) -> bool {
+        match self {
+            Backend::Plain(b) => b.exists(hash),
+            Backend::Encrypted(b) => b.exists(hash),
+        }
+    }
+
+    fn list(&self) -> Result<Box<dyn Iterator<Item = Hash>>, Self::Error> {
+        match self {
+            Backend::Plain(b) => b.list().map_err(BackendError::Plain),
+            Backend::Encrypted(b) => b.list().map_err(BackendError::Encrypted),
+        }
+    }
 }
 
 pub struct FileStore<'repo> {
     repo: &'repo Repository,
+    mmap_threshold: u64,
+    disable_mmap: bool,
+    encryption_key: Option<[u8; encryption::KEY_LEN]>,
 }
 
 impl<'repo> FileStore<'repo> {
     pub fn new(repo: &'repo Repository) -> FileStore<'repo> {
-        FileStore { repo }
+        FileStore {
+            repo,
+            mmap_threshold: mmap_io::DEFAULT_MMAP_THRESHOLD,
+            disable_mmap: false,
+            encryption_key: None,
+        }
+    }
+
+    /// Build a `FileStore` using the mmap threshold and force-off switch
+    /// from `options`. When `options.encryption_passphrase` is set, objects
+    /// are transparently encrypted at rest using a key derived from it,
+    /// generating the repo's keyfile on first use.
+    pub fn with_options(
+        repo: &'repo Repository,
+        options: &crate::Options,
+    ) -> Result<FileStore<'repo>, Error> {
+        let mut store = FileStore {
+            repo,
+            mmap_threshold: options.mmap_threshold,
+            disable_mmap: options.disable_mmap,
+            encryption_key: None,
+        };
+
+        if let Some(passphrase) = &options.encryption_passphrase {
+            store.encryption_key = Some(store.load_or_create_key(passphrase)?);
+        }
+
+        Ok(store)
     }
 
     pub fn store_path(&self) -> PathBuf {
         self.repo.path().join("store")
     }
 
+    fn keyfile_path(&self) -> PathBuf {
+        self.repo.path().join("metadata").join("keyfile")
+    }
+
+    /// Load the store's keyfile (generating and persisting one on first
+    /// use), then derive the object-encryption key from `passphrase`.
+    fn load_or_create_key(&self, passphrase: &str) -> Result<[u8; encryption::KEY_LEN], Error> {
+        let keyfile =
+            encryption::load_or_create_keyfile(&self.keyfile_path()).map_err(Error::Keyfile)?;
+        keyfile.derive_key(passphrase).map_err(Error::Keyfile)
+    }
+
+    /// The backend objects (chunks and manifests) are read from and written
+    /// to: the local filesystem, transparently encrypted when the store was
+    /// built with an encryption key.
+    fn backend(&self) -> Backend {
+        let fs_backend = FilesystemBackend::with_mmap_options(
+            self.store_path().join("objects"),
+            self.mmap_threshold,
+            self.disable_mmap,
+        );
+        match self.encryption_key {
+            Some(key) => Backend::Encrypted(EncryptingBackend::new(fs_backend, key)),
+            None => Backend::Plain(fs_backend),
+        }
+    }
+
     fn get_metadata_path(&self, path: &PathBuf) -> PathBuf {
         self.store_path()
             .join("paths")
             .join(self.repo.branch())
             .join(URL_SAFE.encode(path.display().to_string().as_bytes()))
     }
+
+    fn get_metadata_sig_path(&self, path: &PathBuf) -> PathBuf {
+        let mut sig_path = self.get_metadata_path(path).into_os_string();
+        sig_path.push(".sig");
+        PathBuf::from(sig_path)
+    }
+
+    /// Sign a tracked file's current metadata with `signing_key`, writing
+    /// the detached signature to `<metafile>.sig`.
+    pub fn sign_metadata(&self, path: &PathBuf, signing_key: &SigningKey) -> Result<(), Error> {
+        let metadata_path = self.get_metadata_path(path);
+        let bytes = std::fs::read(&metadata_path)
+            .map_err(|e| Error::FailedToReadFileMetadata(e, path.clone()))?;
+
+        let signature = signing::sign(signing_key, &bytes);
+        std::fs::write(self.get_metadata_sig_path(path), signature)
+            .map_err(|e| Error::FailedToWriteFileMetadata(e, path.clone()))
+    }
+
+    /// Verify a tracked file's metadata against its stored signature and
+    /// `verifying_key`.
+    pub fn verify_metadata(&self, path: &PathBuf, verifying_key: &VerifyingKey) -> Result<(), Error> {
+        let metadata_path = self.get_metadata_path(path);
+        let bytes = std::fs::read(&metadata_path)
+            .map_err(|e| Error::FailedToReadFileMetadata(e, path.clone()))?;
+        let signature = std::fs::read(self.get_metadata_sig_path(path))
+            .map_err(|e| Error::FailedToReadFileMetadata(e, path.clone()))?;
+
+        signing::verify(verifying_key, &bytes, &signature)
+            .map_err(|e| Error::VerificationFailed(e, metadata_path))
+    }
 }
 impl<'repo> Store for FileStore<'repo> {
     type Error = Error;
@@ -130,24 +274,11 @@ impl<'repo> Store for FileStore<'repo> {
             .relative_path(path)
             .ok_or(Error::PathIsntInRepository(path.clone()))?;
 
-        let mut hasher = blake3::Hasher::new();
-        let file = File::open(&path).map_err(|e| Error::FailedToReadFile(e, path.clone()))?;
-        let mut reader = BufReader::new(file);
-
-        std::io::copy(&mut reader, &mut hasher)
+        let bytes = mmap_io::read(&path, &path, self.mmap_threshold, self.disable_mmap)
             .map_err(|e| Error::FailedToReadFile(e, path.clone()))?;
 
-        let hash = hasher.finalize();
+        let object_path = self.write_chunked_object(&bytes)?;
 
-        if !self.store_path().join("objects").exists() {
-            std::fs::create_dir_all(self.store_path().join("objects"))
-                .map_err(Error::FailedToCreateStoreDir)?;
-        }
-        let obj_path = self.store_path().join("objects").join(hash.to_string());
-        std::fs::copy(&path, &obj_path)
-            .map_err(|e| Error::FailedToCopyFile(e, path.clone(), obj_path.clone()))?;
-
-        let object_path = ObjectPath(hash);
         self.add_step_to_metadata(&path, object_path.clone())?;
 
         Ok(object_path)
@@ -163,9 +294,21 @@ impl<'repo> Store for FileStore<'repo> {
             .get(*path.step() as usize)
             .ok_or(Error::PathDoesntExist(path.clone()))?;
 
-        let path = self.store_path().join("objects").join(obj_path.to_string());
-
-        Ok(std::fs::read(path).map_err(|e| Error::FailedToReadObject(e, obj_path.clone()))?)
+        let bytes = self.read_object(obj_path)?;
+
+        match Manifest::from_bytes(&bytes) {
+            // Reassemble the file by concatenating its chunks in order.
+            Some(manifest) => {
+                let mut out = Vec::with_capacity(manifest.chunks.iter().map(|c| c.len).sum::<u64>() as usize);
+                for chunk in &manifest.chunks {
+                    let chunk_path = ObjectPath(chunk.hash);
+                    out.extend_from_slice(&self.read_object(&chunk_path)?);
+                }
+                Ok(out)
+            }
+            // Pre-chunking object: the whole file was stored as-is.
+            None => Ok(bytes),
+        }
     }
 
     /// Write an object to the store.
@@ -176,19 +319,58 @@ impl<'repo> Store for FileStore<'repo> {
     ) -> Result<(), Self::Error> {
         let path = path.as_ref();
 
-        let meta = self.get_metadata(path.relative_path())?;
-        let obj_path = meta
-            .steps
-            .get(*path.step() as usize)
-            .ok_or(Error::PathDoesntExist(path.clone()))?;
-        let path = self.store_path().join("objects").join(obj_path.to_string());
+        let mut meta = self.get_metadata(path.relative_path())?;
+        let step = *path.step() as usize;
+        if step >= meta.steps.len() {
+            return Err(Error::PathDoesntExist(path.clone()));
+        }
+
+        let object_path = self.write_chunked_object(content.as_ref())?;
+        meta.steps[step] = object_path;
+        self.write_metadata(path.relative_path(), meta)
+    }
+}
+
+impl<'repo> FileStore<'repo> {
+    /// Read the raw bytes of a single object (a chunk or a manifest) via the
+    /// object backend.
+    fn read_object(&self, obj_path: &ObjectPath) -> Result<Vec<u8>, Error> {
+        self.backend()
+            .get(obj_path.hash())
+            .map_err(|e| Error::FailedToReadObject(e, obj_path.clone()))
+    }
+
+    /// Write a single object's bytes via the object backend, which skips the
+    /// write if that hash is already present (content-addressed dedup).
+    fn write_object(&self, hash: Hash, bytes: &[u8]) -> Result<(), Error> {
+        self.backend()
+            .put(&hash, bytes)
+            .map_err(|e| Error::FailedToWriteToObject(e, ObjectPath(hash)))
+    }
+
+    /// Split `bytes` into content-defined chunks, write each chunk to the
+    /// store (deduplicating against anything already there), then write and
+    /// return the manifest object that lists them in order.
+    fn write_chunked_object(&self, bytes: &[u8]) -> Result<ObjectPath, Error> {
+        let chunks = chunk::chunks(bytes);
+
+        let mut offset = 0;
+        for c in &chunks {
+            let chunk_bytes = &bytes[offset..offset + c.len as usize];
+            self.write_object(c.hash, chunk_bytes)?;
+            offset += c.len as usize;
+        }
+
+        let manifest = Manifest::new(chunks);
+        let manifest_bytes = manifest.to_bytes();
+        let manifest_hash = blake3::hash(&manifest_bytes);
+        self.write_object(manifest_hash, &manifest_bytes)?;
 
-        Ok(std::fs::write(path, content)
-            .map_err(|e| Error::FailedToWriteToObject(e, obj_path.clone()))?)
+        Ok(ObjectPath(manifest_hash))
     }
 }
 
-fn parse_metadata(mut bytes: impl Iterator<Item = u8>) -> FileMeta {
+pub(crate) fn parse_metadata(mut bytes: impl Iterator<Item = u8>) -> FileMeta {
     let mut steps = Vec::new();
 
     let mut next_chunk = || -> Option<[u8; blake3::OUT_LEN]> {
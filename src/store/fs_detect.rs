@@ -0,0 +1,40 @@
+//! Detecting whether a path lives on a network filesystem.
+//!
+//! Memory-mapped IO behaves badly over NFS/CIFS/SMB/FUSE mounts: pages can
+//! go stale, and a server hiccup while a mapping is live turns into a
+//! `SIGBUS` instead of a normal IO error. Callers use this to fall back to
+//! buffered reads on those filesystems.
+
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+pub fn is_network_filesystem(path: &Path) -> bool {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    // `f_type` magic numbers for network/virtual filesystems, from linux/magic.h.
+    const NFS_SUPER_MAGIC: i64 = 0x6969;
+    const SMB_SUPER_MAGIC: i64 = 0x517b;
+    const CIFS_MAGIC_NUMBER: i64 = 0xff53_4d42u32 as i64;
+    const FUSE_SUPER_MAGIC: i64 = 0x6573_5546;
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+    let result = unsafe { libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if result != 0 {
+        return false;
+    }
+
+    let f_type = unsafe { stat.assume_init() }.f_type as i64;
+    matches!(
+        f_type,
+        NFS_SUPER_MAGIC | SMB_SUPER_MAGIC | CIFS_MAGIC_NUMBER | FUSE_SUPER_MAGIC
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_network_filesystem(_path: &Path) -> bool {
+    false
+}
@@ -0,0 +1,120 @@
+//! Garbage collection ("vacuum") for objects no branch references anymore.
+//!
+//! Steps that get superseded, and chunks that stop being shared, otherwise
+//! accumulate in `store/objects` forever. [`vacuum`] walks every branch's
+//! metadata under `store/paths/*`, builds the set of every object hash still
+//! referenced (including the chunks inside any manifest), and removes
+//! everything else.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use blake3::Hash;
+use thiserror::Error;
+
+use super::{
+    backend::{FilesystemBackend, MaybeEncryptedBackend, ObjectBackend},
+    chunk::Manifest,
+    encryption, file_store,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read store directory '{1}'")]
+    FailedToReadDir(#[source] std::io::Error, PathBuf),
+    #[error("Failed to read metadata file '{1}'")]
+    FailedToReadMetadata(#[source] std::io::Error, PathBuf),
+}
+
+/// What a [`vacuum`] run did, or would do in dry-run mode.
+#[derive(Debug, Default)]
+pub struct VacuumReport {
+    /// Hashes of the objects removed (or that would be removed).
+    pub removed: Vec<Hash>,
+    pub bytes_reclaimed: u64,
+}
+
+/// Delete every object in `store_path/objects` that isn't referenced by any
+/// branch's metadata. When `dry_run` is set, nothing is actually deleted;
+/// the report still lists what would have been.
+///
+/// `encryption_key` must be the same key (or `None`) the store's `FileStore`
+/// uses, so manifests can be decrypted to find the chunks they reference.
+/// Passing the wrong key (or no key for an encrypted store) would make every
+/// manifest unreadable, and this function would then delete chunks that are
+/// still very much live — so it refuses to guess.
+pub fn vacuum(
+    store_path: &PathBuf,
+    dry_run: bool,
+    encryption_key: Option<[u8; encryption::KEY_LEN]>,
+) -> Result<VacuumReport, Error> {
+    let backend = MaybeEncryptedBackend::new(store_path.join("objects"), encryption_key);
+    let live = live_objects(store_path, &backend)?;
+
+    let objects_dir = store_path.join("objects");
+    let backend = FilesystemBackend::new(objects_dir.clone());
+
+    let mut report = VacuumReport::default();
+
+    let Ok(hashes) = backend.list() else {
+        // A missing objects directory already lists as empty; one that
+        // can't be read is treated as having nothing to reclaim.
+        return Ok(report);
+    };
+
+    for hash in hashes {
+        if live.contains(&hash) {
+            continue;
+        }
+
+        let obj_path = objects_dir.join(hash.to_string());
+        let len = std::fs::metadata(&obj_path).map(|m| m.len()).unwrap_or(0);
+
+        if !dry_run {
+            let _ = std::fs::remove_file(&obj_path);
+        }
+
+        report.removed.push(hash);
+        report.bytes_reclaimed += len;
+    }
+
+    Ok(report)
+}
+
+/// Collect every object hash referenced by any branch's metadata: every
+/// step of every tracked file, plus the chunks inside every manifest a step
+/// points at.
+fn live_objects<B: ObjectBackend>(store_path: &PathBuf, backend: &B) -> Result<HashSet<Hash>, Error> {
+    let mut live = HashSet::new();
+
+    // The all-zero hash marks a file as removed at that step; it was never a
+    // real object, so it must never be treated as a live reference.
+    let removed_sentinel = Hash::from_bytes([0; blake3::OUT_LEN]);
+
+    let metadata_paths =
+        super::metadata_file_paths(store_path).map_err(|(e, p)| Error::FailedToReadDir(e, p))?;
+
+    for path in metadata_paths {
+        let bytes =
+            std::fs::read(&path).map_err(|e| Error::FailedToReadMetadata(e, path.clone()))?;
+        let meta = file_store::parse_metadata(bytes.into_iter());
+
+        for step in meta.steps {
+            let hash = *step.hash();
+            if hash == removed_sentinel {
+                continue;
+            }
+
+            live.insert(hash);
+
+            // Manifests are only readable through `backend`: on an
+            // encrypted store, the bytes on disk are ciphertext.
+            if let Ok(bytes) = backend.get(&hash) {
+                if let Some(manifest) = Manifest::from_bytes(&bytes) {
+                    live.extend(manifest.chunks.into_iter().map(|c| c.hash));
+                }
+            }
+        }
+    }
+
+    Ok(live)
+}
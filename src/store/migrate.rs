@@ -0,0 +1,151 @@
+//! Moving objects between [`ObjectBackend`]s, e.g. local filesystem to S3
+//! and back, without having to trust that every referenced object is still
+//! present on the source.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{path::ObjectPath, store::chunk::Manifest};
+
+use super::{
+    backend::{self, MaybeEncryptedBackend, ObjectBackend},
+    encryption, file_store, FileMeta,
+};
+
+#[derive(Debug, Error)]
+pub enum Error<SE, DE> {
+    #[error("Failed to read object from the source backend")]
+    Source(#[source] SE),
+    #[error("Failed to write object to the destination backend")]
+    Dest(#[source] DE),
+    #[error("Failed to read store directory '{1}'")]
+    ReadDir(#[source] std::io::Error, PathBuf),
+    #[error("Failed to read metadata file '{1}'")]
+    ReadMetadata(#[source] std::io::Error, PathBuf),
+}
+
+/// Outcome of a [`migrate`] run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationSummary {
+    pub copied: u64,
+    pub skipped: u64,
+    pub missing: u64,
+}
+
+/// Migrate every object referenced anywhere in the repo at `store_path`: every
+/// branch's tracked files, every step of each, from the store's own objects
+/// directory to `dest`.
+///
+/// `source_key` must be the same key (or `None`) the store's `FileStore`
+/// uses. Manifests are read through a [`MaybeEncryptedBackend`] built from
+/// it, not raw off disk, so chunk references inside them can actually be
+/// followed on an encrypted store. A wrong key makes the first object read
+/// fail with [`Error::Source`]; no key for an encrypted store copies each
+/// step's ciphertext as-is and, since no manifest parses, silently migrates
+/// only the top-level objects.
+pub fn migrate_repo<D: ObjectBackend>(
+    store_path: &Path,
+    source_key: Option<[u8; encryption::KEY_LEN]>,
+    dest: &D,
+    skip_missing_objects: bool,
+) -> Result<MigrationSummary, Error<backend::maybe_encrypted::Error, D::Error>> {
+    let steps = all_tracked_steps(store_path)?;
+    let source = MaybeEncryptedBackend::new(store_path.join("objects"), source_key);
+    migrate(&source, dest, steps, skip_missing_objects)
+}
+
+/// Collect every step of every tracked file, across every branch, by reading
+/// each branch's metadata directly off disk (mirroring [`gc::vacuum`](super::gc::vacuum)'s walk).
+fn all_tracked_steps<SE, DE>(store_path: &Path) -> Result<Vec<ObjectPath>, Error<SE, DE>> {
+    let mut steps = Vec::new();
+
+    let metadata_paths =
+        super::metadata_file_paths(store_path).map_err(|(e, p)| Error::ReadDir(e, p))?;
+
+    for path in metadata_paths {
+        let bytes = std::fs::read(&path).map_err(|e| Error::ReadMetadata(e, path.clone()))?;
+        let meta = file_store::parse_metadata(bytes.into_iter());
+        steps.extend(meta.steps);
+    }
+
+    Ok(steps)
+}
+
+/// Copy every object referenced by `steps` (a file's `FileMeta.steps`, across
+/// every generation being migrated) from `source` to `dest`, one object at a
+/// time. Manifests are followed so their chunks are migrated too. Writes are
+/// skipped when `dest` already has the hash. When `skip_missing_objects` is
+/// set, an object referenced by metadata but absent from `source` is logged
+/// and counted rather than aborting the migration.
+pub fn migrate<S: ObjectBackend, D: ObjectBackend>(
+    source: &S,
+    dest: &D,
+    steps: impl IntoIterator<Item = ObjectPath>,
+    skip_missing_objects: bool,
+) -> Result<MigrationSummary, Error<S::Error, D::Error>> {
+    let mut summary = MigrationSummary::default();
+
+    for step in steps {
+        migrate_object(source, dest, step.hash(), skip_missing_objects, &mut summary)?;
+    }
+
+    Ok(summary)
+}
+
+fn migrate_object<S: ObjectBackend, D: ObjectBackend>(
+    source: &S,
+    dest: &D,
+    hash: &blake3::Hash,
+    skip_missing_objects: bool,
+    summary: &mut MigrationSummary,
+) -> Result<(), Error<S::Error, D::Error>> {
+    if dest.exists(hash) {
+        summary.skipped += 1;
+
+        // The object itself is already at the destination, but a prior
+        // interrupted run may have copied a manifest without its chunks;
+        // recurse so those still get backfilled.
+        if let Ok(bytes) = dest.get(hash) {
+            if let Some(manifest) = Manifest::from_bytes(&bytes) {
+                for chunk in manifest.chunks {
+                    migrate_object(source, dest, &chunk.hash, skip_missing_objects, summary)?;
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    if !source.exists(hash) {
+        if skip_missing_objects {
+            warn!(%hash, "Object referenced by metadata is missing from the source backend, skipping");
+            summary.missing += 1;
+            return Ok(());
+        }
+    }
+
+    let bytes = source.get(hash).map_err(Error::Source)?;
+    dest.put(hash, &bytes).map_err(Error::Dest)?;
+    summary.copied += 1;
+
+    // A step's object may be a chunk manifest; migrate its chunks too so the
+    // destination backend can fully reassemble the file on its own.
+    if let Some(manifest) = Manifest::from_bytes(&bytes) {
+        for chunk in manifest.chunks {
+            migrate_object(source, dest, &chunk.hash, skip_missing_objects, summary)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect every `ObjectPath` referenced across a set of files' metadata,
+/// i.e. every step of every generation being migrated.
+pub fn referenced_objects(files: impl IntoIterator<Item = FileMeta>) -> Vec<ObjectPath> {
+    files
+        .into_iter()
+        .flat_map(|meta| meta.steps.into_iter())
+        .collect()
+}
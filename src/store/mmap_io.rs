@@ -0,0 +1,63 @@
+//! Zero-copy reads for large files.
+//!
+//! Reading a large file via `std::fs::read` copies it once into a fresh
+//! heap buffer. Memory-mapping it instead lets the chunker and hasher work
+//! directly against the page cache, at the cost of behaving badly on
+//! network mounts (see [`fs_detect`](super::fs_detect)), so it's only used
+//! above a size threshold and can be force-disabled.
+
+use std::{fs::File, io, ops::Deref, path::Path};
+
+use memmap2::Mmap;
+
+use super::fs_detect::is_network_filesystem;
+
+/// Files at least this many bytes are memory-mapped instead of read into a
+/// buffer; matches `Options::mmap_threshold`'s default.
+pub const DEFAULT_MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// Bytes read from disk, either a zero-copy memory map or an owned buffer.
+pub enum MappedBytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for MappedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MappedBytes::Mapped(mmap) => mmap,
+            MappedBytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Read `path`, memory-mapping it when it's at least `threshold` bytes and
+/// not on a network filesystem. Falls back to a normal buffered read
+/// otherwise, or unconditionally when `force_off` is set.
+///
+/// `network_check_path` is what gets `statfs`'d for network-filesystem
+/// detection. It's usually the same as `path`, but callers that map a file
+/// living under a directory they already know the filesystem of (e.g. an
+/// object backend mapping individual objects under `store/objects`) can
+/// pass that directory instead, so the check doesn't depend on the mapped
+/// file still existing or being reachable by the same route.
+pub fn read(path: &Path, network_check_path: &Path, threshold: u64, force_off: bool) -> io::Result<MappedBytes> {
+    if force_off {
+        return std::fs::read(path).map(MappedBytes::Owned);
+    }
+
+    let len = std::fs::metadata(path)?.len();
+    if len < threshold || is_network_filesystem(network_check_path) {
+        return std::fs::read(path).map(MappedBytes::Owned);
+    }
+
+    let file = File::open(path)?;
+    // SAFETY: mmap is inherently unsound if another process truncates the
+    // file while it's mapped; we accept that risk here the same way any
+    // mmap-based reader does, rather than trying to prevent it from a
+    // library-level API.
+    let mmap = unsafe { Mmap::map(&file)? };
+    Ok(MappedBytes::Mapped(mmap))
+}
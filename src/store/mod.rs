@@ -1,10 +1,18 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use blake3::Hash;
 
 use crate::path::{ObjectPath, RepoPath};
 
+pub mod backend;
+pub mod chunk;
+pub mod encryption;
 pub mod file_store;
+pub mod fs_detect;
+pub mod gc;
+pub mod migrate;
+pub mod mmap_io;
+pub mod signing;
 
 pub trait Store {
     type Error: std::error::Error;
@@ -69,3 +77,41 @@ pub trait Store {
 pub struct FileMeta {
     pub steps: Vec<ObjectPath>,
 }
+
+/// List the metadata file of every tracked file, across every branch, under
+/// `store_path/paths`. Sidecars living alongside metadata files (currently
+/// just [`FileStore::sign_metadata`](file_store::FileStore::sign_metadata)'s
+/// `.sig` files) are excluded, since they aren't themselves metadata and
+/// `file_store::parse_metadata` would otherwise misread their bytes as bogus
+/// steps.
+///
+/// On error, returns the failing directory alongside the `io::Error` so
+/// callers can wrap it in their own error type with context.
+pub(crate) fn metadata_file_paths(store_path: &Path) -> Result<Vec<PathBuf>, (std::io::Error, PathBuf)> {
+    let mut paths = Vec::new();
+
+    let paths_dir = store_path.join("paths");
+    if !paths_dir.exists() {
+        return Ok(paths);
+    }
+
+    let branches = paths_dir
+        .read_dir()
+        .map_err(|e| (e, paths_dir.clone()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()));
+
+    for branch in branches {
+        let branch_dir = branch.path();
+        let files = branch_dir
+            .read_dir()
+            .map_err(|e| (e, branch_dir.clone()))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+            .filter(|entry| entry.path().extension().map_or(true, |ext| ext != "sig"));
+
+        paths.extend(files.map(|entry| entry.path()));
+    }
+
+    Ok(paths)
+}
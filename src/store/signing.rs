@@ -0,0 +1,32 @@
+//! Detached Ed25519 signatures over metadata bytes.
+//!
+//! A repo's generations file and each file's per-step metadata can be
+//! signed with a repo signing key; the signature is stored alongside the
+//! data it covers (e.g. `generations.sig`, `<metafile>.sig`) so a store
+//! synced through an untrusted backend can be verified before its objects
+//! are trusted.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Signature does not match the signed data")]
+    VerificationFailed,
+    #[error("Malformed signature")]
+    MalformedSignature,
+}
+
+/// Sign `bytes`, returning the detached signature to store alongside it.
+pub fn sign(signing_key: &SigningKey, bytes: &[u8]) -> [u8; 64] {
+    signing_key.sign(bytes).to_bytes()
+}
+
+/// Verify that `signature` is a valid signature over `bytes` made by the
+/// holder of `verifying_key`.
+pub fn verify(verifying_key: &VerifyingKey, bytes: &[u8], signature: &[u8]) -> Result<(), Error> {
+    let signature = Signature::from_slice(signature).map_err(|_| Error::MalformedSignature)?;
+    verifying_key
+        .verify(bytes, &signature)
+        .map_err(|_| Error::VerificationFailed)
+}